@@ -0,0 +1,72 @@
+//! On-disk cache of decoded frames, keyed by a fingerprint of the input file
+//! plus the scale factor.
+
+use crate::qoi;
+use image::RgbImage;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::path::{Path, PathBuf};
+
+const CACHE_ROOT: &str = ".dsplit-cache";
+
+/// Directory that a given `(input, scale)` pair's cached frames live in.
+pub fn dir_for(input: impl AsRef<Path>, scale: f64) -> io::Result<PathBuf> {
+    Ok(Path::new(CACHE_ROOT).join(key(input.as_ref(), scale)?))
+}
+
+/// Cheap fingerprint of the input file's identity (path, size, mtime) plus
+/// scale -- not a content hash, since hashing the whole video would defeat
+/// the point of caching it.
+fn key(input: &Path, scale: f64) -> io::Result<String> {
+    let meta = std::fs::metadata(input)?;
+    let mut hasher = DefaultHasher::new();
+
+    input.canonicalize().unwrap_or_else(|_| input.to_path_buf()).hash(&mut hasher);
+    meta.len().hash(&mut hasher);
+    meta.modified()?
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+        .hash(&mut hasher);
+    scale.to_bits().hash(&mut hasher);
+
+    Ok(format!("{:016x}", hasher.finish()))
+}
+
+/// Loads a previously-cached frame sequence from `dir`, if one is present.
+pub fn load(dir: impl AsRef<Path>) -> Option<Vec<RgbImage>> {
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(&dir)
+        .ok()?
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| path.extension().map_or(false, |ext| ext == "qoi"))
+        .collect();
+
+    if paths.is_empty() {
+        return None;
+    }
+
+    paths.sort();
+
+    paths
+        .into_iter()
+        .map(|path| qoi::decode(&std::fs::read(path).ok()?).ok())
+        .collect()
+}
+
+/// Writes a frame sequence to `dir` as QOI, one file per frame. Each file is
+/// written to a temp path and renamed into place so a run killed mid-write
+/// never leaves a truncated `.qoi` file for `load` to trip over.
+pub fn store(dir: impl AsRef<Path>, frames: &[RgbImage]) -> io::Result<()> {
+    std::fs::create_dir_all(&dir)?;
+
+    for (idx, frame) in frames.iter().enumerate() {
+        let final_path = dir.as_ref().join(format!("{:05}.qoi", idx));
+        let tmp_path = dir.as_ref().join(format!("{:05}.qoi.tmp", idx));
+
+        std::fs::write(&tmp_path, qoi::encode(frame))?;
+        std::fs::rename(&tmp_path, &final_path)?;
+    }
+
+    Ok(())
+}
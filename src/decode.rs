@@ -0,0 +1,122 @@
+//! In-process video decoding via `ffmpeg-next`.
+
+use ffmpeg_next as ffmpeg;
+use image::RgbImage;
+use std::path::Path;
+
+/// Stream dimensions and frame count, queried without decoding any frames.
+#[derive(Clone, Copy, Debug)]
+pub struct VideoInfo {
+    pub width: u32,
+    pub height: u32,
+    /// `None` when the container doesn't report a frame count up front.
+    pub nb_frames: Option<u64>,
+    pub fps: f64,
+}
+
+/// Queries stream dimensions, frame count and frame rate without decoding
+/// anything -- an `ffprobe`-style lookup so the pipeline can preallocate and
+/// report progress before doing any real work.
+pub fn probe(video: impl AsRef<Path>) -> Result<VideoInfo, ffmpeg::Error> {
+    ffmpeg::init()?;
+
+    let ictx = ffmpeg::format::input(&video)?;
+    let stream = ictx
+        .streams()
+        .best(ffmpeg::media::Type::Video)
+        .ok_or(ffmpeg::Error::StreamNotFound)?;
+
+    let decoder = ffmpeg::codec::context::Context::from_parameters(stream.parameters())?
+        .decoder()
+        .video()?;
+
+    let rate = stream.rate();
+    let nb_frames = stream.frames();
+
+    Ok(VideoInfo {
+        width: decoder.width(),
+        height: decoder.height(),
+        nb_frames: if nb_frames > 0 { Some(nb_frames as u64) } else { None },
+        fps: rate.numerator() as f64 / rate.denominator() as f64,
+    })
+}
+
+/// Decodes every frame of `video`, scaling by `scale` and converting to RGB8
+/// along the way, and returns them in order. `info` should come from a prior
+/// [`probe`] call, so the frame dimensions and output `Vec` capacity are
+/// known up front instead of being re-derived from the decoder.
+pub fn decode_frames(video: impl AsRef<Path>, scale: f64, info: &VideoInfo) -> Result<Vec<RgbImage>, ffmpeg::Error> {
+    ffmpeg::init()?;
+
+    let mut ictx = ffmpeg::format::input(&video)?;
+    let input = ictx
+        .streams()
+        .best(ffmpeg::media::Type::Video)
+        .ok_or(ffmpeg::Error::StreamNotFound)?;
+    let video_stream_index = input.index();
+
+    let context_decoder = ffmpeg::codec::context::Context::from_parameters(input.parameters())?;
+    let mut decoder = context_decoder.decoder().video()?;
+
+    let out_width = ((info.width as f64) * scale).round().max(1.) as u32;
+    let out_height = ((info.height as f64) * scale).round().max(1.) as u32;
+
+    let mut scaler = ffmpeg::software::scaling::context::Context::get(
+        decoder.format(),
+        info.width,
+        info.height,
+        ffmpeg::format::Pixel::RGB24,
+        out_width,
+        out_height,
+        ffmpeg::software::scaling::flag::Flags::BILINEAR,
+    )?;
+
+    let mut frames = Vec::with_capacity(info.nb_frames.unwrap_or(0) as usize);
+
+    for (stream, packet) in ictx.packets() {
+        if stream.index() == video_stream_index {
+            decoder.send_packet(&packet)?;
+            drain_decoded(&mut decoder, &mut scaler, out_width, out_height, &mut frames)?;
+        }
+    }
+    decoder.send_eof()?;
+    drain_decoded(&mut decoder, &mut scaler, out_width, out_height, &mut frames)?;
+
+    Ok(frames)
+}
+
+/// Pulls every frame currently buffered in `decoder`, scales it to RGB8 and
+/// appends it to `frames`.
+fn drain_decoded(
+    decoder: &mut ffmpeg::decoder::Video,
+    scaler: &mut ffmpeg::software::scaling::context::Context,
+    width: u32,
+    height: u32,
+    frames: &mut Vec<RgbImage>,
+) -> Result<(), ffmpeg::Error> {
+    let mut decoded = ffmpeg::util::frame::video::Video::empty();
+
+    while decoder.receive_frame(&mut decoded).is_ok() {
+        let mut rgb = ffmpeg::util::frame::video::Video::empty();
+        scaler.run(&decoded, &mut rgb)?;
+        frames.push(frame_to_image(&rgb, width, height));
+    }
+
+    Ok(())
+}
+
+/// Copies a scaled RGB24 ffmpeg frame into an owned `image::RgbImage`,
+/// respecting the decoder's (possibly padded) row stride.
+fn frame_to_image(frame: &ffmpeg::util::frame::video::Video, width: u32, height: u32) -> RgbImage {
+    let stride = frame.stride(0);
+    let data = frame.data(0);
+    let row_bytes = width as usize * 3;
+    let mut buf = Vec::with_capacity(row_bytes * height as usize);
+
+    for row in 0..height as usize {
+        let start = row * stride;
+        buf.extend_from_slice(&data[start..start + row_bytes]);
+    }
+
+    RgbImage::from_raw(width, height, buf).expect("scaled frame had unexpected buffer size")
+}
@@ -2,7 +2,9 @@ use clap::{App, Arg};
 use grid::Grid;
 use image::{buffer::RowsMut, ImageBuffer, Luma, Pixel, Rgb};
 use imageproc::filter::Kernel;
+use indicatif::{ProgressBar, ProgressStyle};
 use lab::Lab;
+use rayon::prelude::*;
 use rgb::AsPixels;
 use std::{io, ops::Deref};
 use std::{
@@ -14,19 +16,25 @@ use std::{
     process::{Command, ExitStatus, Stdio},
 };
 
+mod cache;
+mod decode;
+mod qoi;
+mod scene;
+mod split;
+
 
 #[derive(Clone, Copy, Debug)]
-enum Direction {
+pub(crate) enum Direction {
     Horizontal,
     Vertical,
 }
 
 #[derive(Clone, Copy, Debug)]
-struct Line {
-    dir: Direction,
-    x: usize,
-    y: usize,
-    len: usize,
+pub(crate) struct Line {
+    pub(crate) dir: Direction,
+    pub(crate) x: usize,
+    pub(crate) y: usize,
+    pub(crate) len: usize,
 }
 
 struct GridPair(Grid<f32>, Grid<f32>);
@@ -37,6 +45,30 @@ impl GridPair {
         self.1.iter_mut().for_each(f);
     }
 
+    /// Derives a binarization cutoff from the diff-grid statistics instead of
+    /// a hard-coded magic number: `mean + k * stddev` over every non-zero
+    /// value across both grids. Tracks the resolution/contrast/noise of the
+    /// source instead of assuming one fixed scale of input.
+    fn adaptive_threshold(&self, k: f32) -> f32 {
+        let values: Vec<f32> = self
+            .0
+            .iter()
+            .chain(self.1.iter())
+            .copied()
+            .filter(|v| *v != 0.)
+            .collect();
+
+        if values.is_empty() {
+            return 0.;
+        }
+
+        let mean = values.iter().sum::<f32>() / values.len() as f32;
+        let variance =
+            values.iter().map(|v| (v - mean).powi(2)).sum::<f32>() / values.len() as f32;
+
+        mean + k * variance.sqrt()
+    }
+
     fn lines(&self) -> Vec<Line> {
         let mut lines = vec![];
 
@@ -91,26 +123,28 @@ impl GridPair {
 }
 
 trait Lines {
-    fn discard_shorter_than(&mut self, size: usize);
+    fn discard_shorter_than_fraction(&mut self, fraction: f32, width: usize, height: usize);
     fn to_image(&self, width: usize, height: usize) -> ImageBuffer<Rgb<u8>, Vec<u8>>;
 }
 
 // impl for a generic deref to [Line]
 impl Lines for Vec<Line> {
-    // fn get_shorter_than, non-mutable!
-    fn discard_shorter_than(&mut self, size: usize) {
+    // like the old absolute-pixel-count discard, but `fraction` is a fraction of
+    // the line's own dimension (height for vertical lines, width for
+    // horizontal ones), so it scales with image resolution
+    fn discard_shorter_than_fraction(&mut self, fraction: f32, width: usize, height: usize) {
         *self = self
             .iter()
-            .filter(|line| line.len >= size)
+            .filter(|line| {
+                let relevant_dim = match line.dir {
+                    Direction::Vertical => height,
+                    Direction::Horizontal => width,
+                };
+
+                line.len as f32 >= fraction * relevant_dim as f32
+            })
             .map(|l| *l)
             .collect::<Vec<Line>>();
-
-        // when disregarding short lines they should be blotted out of the grids shouldn't they?
-        // so maybe this lines thing actually needs to hold a GridPair?
-
-        // future me: not if we don't use the diff grids again, see those just store the differences
-        // once we have the lines all the representation is done with those so we don't acutally
-        // need the diff grids anymore unless we're doing a different transform or something, idk
     }
     fn to_image(&self, width: usize, height: usize) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
         let mut img_grid: Grid<[u8; 3]> = Grid::new(height, width);
@@ -166,39 +200,75 @@ fn create_image_sequence(
         .status()
 }
 
-fn main() {
-    // check that ffmpeg exists (before trying to use it)
-    if !ffmpeg_present() {
-        todo!("add code to get ffmpeg");
+/// Reads back the numbered PNG sequence written by `create_image_sequence`,
+/// in frame order.
+fn load_frame_sequence(dir: impl AsRef<Path>) -> io::Result<Vec<image::RgbImage>> {
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| path.extension().map_or(false, |ext| ext == "png"))
+        .collect();
+
+    // frame numbers aren't zero-padded, so sort numerically rather than by name
+    paths.sort_by_key(|path| {
+        path.file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| stem.parse::<u64>().ok())
+    });
+
+    paths
+        .into_iter()
+        .map(|path| Ok(image::open(path).expect("failed to decode sequence frame").to_rgb8()))
+        .collect()
+}
+
+/// Uses ffmpeg's segment muxer to cut `video` into clips at the given cut
+/// timestamps (in seconds).
+fn split_video_at_cuts(video: impl AsRef<Path>, cuts: &[scene::Cut], output: impl AsRef<Path>) -> io::Result<()> {
+    let segment_times = cuts
+        .iter()
+        .map(|cut| cut.timestamp.to_string())
+        .collect::<Vec<_>>()
+        .join(",");
+
+    println!("splitting {:?} at {} detected cuts", video.as_ref(), cuts.len());
+
+    let status = Command::new("ffmpeg")
+        .args(&["-loglevel", "quiet", "-stats"])
+        .arg("-i")
+        .arg(video.as_ref())
+        .args(&["-f", "segment"])
+        .arg("-segment_times")
+        .arg(segment_times)
+        .args(&["-c", "copy", "-reset_timestamps", "1"])
+        .arg(output.as_ref().join("clip_%03d.mp4"))
+        .status()?;
+
+    if status.success() {
+        Ok(())
     } else {
-        println!("found ffmpeg!");
+        Err(io::Error::new(io::ErrorKind::Other, format!("ffmpeg exited with {}", status)))
     }
+}
 
-    let matches = App::new("dsplit")
-        .arg(
-            Arg::with_name("INPUT")
-                .help("The input file")
-                .required(true),
-        )
-        .arg(
-            Arg::with_name("scale")
-                .short("s")
-                .long("scale")
-                .default_value(".1")
-                .help("The scale factor for the size of each video frame (smaller = faster)"),
-        )
-        .get_matches();
-
-    let scale: f64 = matches
-        .value_of("scale")
-        .unwrap()
-        .parse()
-        .expect("failed to parse scale value");
-    let input = matches.value_of("INPUT").unwrap();
+/// Extensions we treat as "this input is a video, not a single image".
+const VIDEO_EXTENSIONS: &[&str] = &["mp4", "mkv", "mov", "avi", "webm"];
 
-    // determine if input is an image or video
+fn is_video(input: impl AsRef<Path>) -> bool {
+    input
+        .as_ref()
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map_or(false, |ext| VIDEO_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
 
-    let img = image::open(input).unwrap().to_rgb8();
+/// Runs the LAB diff-grid / line-detection pipeline on a single frame: builds
+/// the column and row diff grids, binarizes them at `threshold_arg` ("auto"
+/// derives it from the grid stats, otherwise it's parsed as a manual float),
+/// and discards lines shorter than `min_line_fraction` of their dimension.
+///
+/// This is the per-frame unit of work shared by the single-image path and
+/// the parallel per-frame video pipeline.
+fn detect_lines(img: &ImageBuffer<Rgb<u8>, Vec<u8>>, threshold_arg: &str, threshold_k: f32, min_line_fraction: f32) -> Vec<Line> {
     let mut diff_grid_x: Grid<f32> = Grid::new(img.height() as usize, img.width() as usize - 1);
     let mut diff_grid_y: Grid<f32> = Grid::new(img.height() as usize - 1, img.width() as usize);
     let img_grid = Grid::from_vec(
@@ -235,10 +305,242 @@ fn main() {
     }
 
     let mut grid_pair = GridPair(diff_grid_x, diff_grid_y);
-    grid_pair.filter(|value| *value = if *value > 0.0025 /* MAGIC */ { 1. } else { 0. });
+
+    let threshold = if threshold_arg == "auto" {
+        grid_pair.adaptive_threshold(threshold_k)
+    } else {
+        threshold_arg.parse().expect("failed to parse --threshold value")
+    };
+    grid_pair.filter(|value| *value = if *value > threshold { 1. } else { 0. });
 
     let mut lines = grid_pair.lines();
-    lines.discard_shorter_than(20  /* MAGIC */);
+    lines.discard_shorter_than_fraction(min_line_fraction, img.width() as usize, img.height() as usize);
+    lines
+}
+
+fn main() {
+    let matches = App::new("dsplit")
+        .arg(
+            Arg::with_name("INPUT")
+                .help("The input file")
+                .required(true),
+        )
+        .arg(
+            Arg::with_name("scale")
+                .short("s")
+                .long("scale")
+                .default_value(".1")
+                .help("The scale factor for the size of each video frame (smaller = faster)"),
+        )
+        .arg(
+            Arg::with_name("scene_k")
+                .long("scene-k")
+                .default_value("2.0")
+                .help("Stddev multiplier above the rolling mean score required to flag a scene cut"),
+        )
+        .arg(
+            Arg::with_name("min_gap")
+                .long("min-gap")
+                .default_value("10")
+                .help("Minimum number of frames between two detected scene cuts"),
+        )
+        .arg(
+            Arg::with_name("threshold")
+                .short("t")
+                .long("threshold")
+                .default_value("auto")
+                .help("Edge binarization cutoff: 'auto' derives it from the diff-grid stats, or a manual float"),
+        )
+        .arg(
+            Arg::with_name("threshold_k")
+                .long("threshold-k")
+                .default_value("2.0")
+                .help("Stddev multiplier used to derive the cutoff when --threshold is 'auto'"),
+        )
+        .arg(
+            Arg::with_name("min_line_fraction")
+                .long("min-line-fraction")
+                .default_value("0.02")
+                .help("Minimum line length to keep, as a fraction of the relevant image dimension"),
+        )
+        .arg(
+            Arg::with_name("split")
+                .long("split")
+                .help("Crop the image into tiles at the detected cut lines instead of just drawing them"),
+        )
+        .arg(
+            Arg::with_name("span_fraction")
+                .long("span-fraction")
+                .default_value("0.9")
+                .help("Minimum fraction of the image width/height a line must span to count as a cut"),
+        )
+        .arg(
+            Arg::with_name("cluster_distance")
+                .long("cluster-distance")
+                .default_value("10")
+                .help("Cut coordinates within this many pixels of each other are merged into one boundary"),
+        )
+        .arg(
+            Arg::with_name("tiles_dir")
+                .long("tiles-dir")
+                .default_value("tiles")
+                .help("Directory to write cropped tiles into"),
+        )
+        .arg(
+            Arg::with_name("cache")
+                .long("cache")
+                .help("Cache decoded video frames on disk (as QOI) so re-runs skip decoding entirely"),
+        )
+        .get_matches();
+
+    let scale: f64 = matches
+        .value_of("scale")
+        .unwrap()
+        .parse()
+        .expect("failed to parse scale value");
+    let scene_k: f32 = matches
+        .value_of("scene_k")
+        .unwrap()
+        .parse()
+        .expect("failed to parse scene-k value");
+    let min_gap: usize = matches
+        .value_of("min_gap")
+        .unwrap()
+        .parse()
+        .expect("failed to parse min-gap value");
+    let threshold_arg = matches.value_of("threshold").unwrap();
+    let threshold_k: f32 = matches
+        .value_of("threshold_k")
+        .unwrap()
+        .parse()
+        .expect("failed to parse threshold-k value");
+    let min_line_fraction: f32 = matches
+        .value_of("min_line_fraction")
+        .unwrap()
+        .parse()
+        .expect("failed to parse min-line-fraction value");
+    let do_split = matches.is_present("split");
+    let span_fraction: f32 = matches
+        .value_of("span_fraction")
+        .unwrap()
+        .parse()
+        .expect("failed to parse span-fraction value");
+    let cluster_distance: usize = matches
+        .value_of("cluster_distance")
+        .unwrap()
+        .parse()
+        .expect("failed to parse cluster-distance value");
+    let tiles_dir = matches.value_of("tiles_dir").unwrap();
+    let use_cache = matches.is_present("cache");
+    let input = matches.value_of("INPUT").unwrap();
+
+    // determine if input is an image or video
+
+    if is_video(input) {
+        // ffmpeg-next handles decoding, but splitting the final clips still
+        // shells out to the ffmpeg binary, so it has to be on PATH for video
+        if !ffmpeg_present() {
+            todo!("add code to get ffmpeg");
+        }
+
+        let cache_dir = if use_cache {
+            cache::dir_for(input, scale).ok()
+        } else {
+            None
+        };
+        let cached_frames = cache_dir.as_ref().and_then(|dir| cache::load(dir));
+
+        let (frames, fps, nb_frames_hint) = if let Some(frames) = cached_frames {
+            println!("loaded {} cached frames from {:?}", frames.len(), cache_dir.as_ref().unwrap());
+
+            let fps = decode::probe(input).map(|info| info.fps).unwrap_or(30.0);
+            let n = frames.len() as u64;
+            (frames, fps, Some(n))
+        } else {
+            // prefer decoding in-process via ffmpeg-next; only fall back to
+            // the CLI sequence dump if probing the input with ffmpeg-next fails
+            let (frames, fps, nb_frames_hint) = match decode::probe(input) {
+                Ok(info) => {
+                    println!(
+                        "probed {}x{} video, {} frames @ {:.2} fps",
+                        info.width,
+                        info.height,
+                        info.nb_frames.map_or("?".to_string(), |n| n.to_string()),
+                        info.fps
+                    );
+
+                    let frames = decode::decode_frames(input, scale, &info)
+                        .expect("failed to decode video via ffmpeg-next");
+                    (frames, info.fps, info.nb_frames)
+                }
+                Err(err) => {
+                    println!(
+                        "ffmpeg-next unavailable ({}), falling back to ffmpeg CLI sequence dump",
+                        err
+                    );
+
+                    let sequence_dir = Path::new("sequence");
+                    std::fs::create_dir_all(sequence_dir).expect("failed to create sequence dir");
+                    create_image_sequence(input, scale, sequence_dir)
+                        .expect("failed to create image sequence");
+
+                    let frames = load_frame_sequence(sequence_dir)
+                        .expect("failed to read back image sequence");
+                    (frames, 30.0, None) // TODO: probe the real frame rate when falling back to the CLI path
+                }
+            };
+
+            if let Some(dir) = &cache_dir {
+                cache::store(dir, &frames).expect("failed to write frame cache");
+            }
+
+            (frames, fps, nb_frames_hint)
+        };
+
+        let cuts = scene::detect_cuts(&frames, scene_k, min_gap, fps);
+
+        println!("{:?}", cuts);
+
+        // the line-detection pipeline is embarrassingly parallel across
+        // frames, so fan it out across all cores instead of a serial loop
+        let progress = ProgressBar::new(nb_frames_hint.unwrap_or(frames.len() as u64));
+        progress.set_style(
+            ProgressStyle::with_template(
+                "{spinner} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} frames ({eta})",
+            )
+            .unwrap()
+            .progress_chars("#>-"),
+        );
+
+        let per_frame_lines: Vec<Vec<Line>> = frames
+            .par_iter()
+            .map(|frame| {
+                let lines = detect_lines(frame, threshold_arg, threshold_k, min_line_fraction);
+                progress.inc(1);
+                lines
+            })
+            .collect();
+
+        progress.finish_with_message("done analyzing frames");
+
+        if do_split {
+            for (idx, (frame, lines)) in frames.iter().zip(per_frame_lines.iter()).enumerate() {
+                let frame_tiles_dir = Path::new(tiles_dir).join(format!("frame_{:05}", idx));
+                split::split_into_tiles(lines, frame, span_fraction, cluster_distance, frame_tiles_dir)
+                    .expect("failed to split frame into tiles");
+            }
+        }
+
+        if cuts.is_empty() {
+            println!("no scene cuts detected, leaving {:?} unsplit", input);
+        } else {
+            split_video_at_cuts(input, &cuts, ".").expect("failed to split video at detected cuts");
+        }
+        return;
+    }
+
+    let img = image::open(input).unwrap().to_rgb8();
+    let lines = detect_lines(&img, threshold_arg, threshold_k, min_line_fraction);
 
     println!("{:?}", lines);
 
@@ -247,6 +549,13 @@ fn main() {
         .save("edges.png")
         .unwrap();
 
+    if do_split {
+        let rects = split::split_into_tiles(&lines, &img, span_fraction, cluster_distance, tiles_dir)
+            .expect("failed to split image into tiles");
+
+        println!("{:?}", rects);
+    }
+
     // might need to keep the original GridPair for later, if so, derive Clone and clone here
 
 /*     image::ImageBuffer::<Luma<u8>, Vec<u8>>::from_raw(
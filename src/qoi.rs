@@ -0,0 +1,202 @@
+//! A minimal QOI (Quite OK Image) codec for 8-bit RGB images, no alpha.
+
+use image::RgbImage;
+
+const QOI_OP_INDEX: u8 = 0x00; // 00xxxxxx
+const QOI_OP_DIFF: u8 = 0x40; // 01xxxxxx
+const QOI_OP_LUMA: u8 = 0x80; // 10xxxxxx
+const QOI_OP_RUN: u8 = 0xc0; // 11xxxxxx
+const QOI_OP_RGB: u8 = 0xfe;
+const QOI_MASK_2: u8 = 0xc0;
+const HEADER_LEN: usize = 14;
+const END_MARKER: [u8; 7] = [0, 0, 0, 0, 0, 0, 1];
+
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+struct Pixel {
+    r: u8,
+    g: u8,
+    b: u8,
+}
+
+fn hash(p: Pixel) -> usize {
+    // alpha is always 255 (opaque), so its term is the constant 11 * 255
+    (p.r as usize * 3 + p.g as usize * 5 + p.b as usize * 7 + 255 * 11) % 64
+}
+
+/// Encodes an RGB8 image as a QOI byte stream.
+pub fn encode(img: &RgbImage) -> Vec<u8> {
+    let (width, height) = img.dimensions();
+    let pixels: Vec<Pixel> = img.pixels().map(|p| Pixel { r: p[0], g: p[1], b: p[2] }).collect();
+
+    let mut out = Vec::with_capacity(HEADER_LEN + pixels.len() * 2);
+    out.extend_from_slice(b"qoif");
+    out.extend_from_slice(&width.to_be_bytes());
+    out.extend_from_slice(&height.to_be_bytes());
+    out.push(3); // channels
+    out.push(0); // colorspace (unused by this reader/writer)
+
+    let mut index = [Pixel::default(); 64];
+    let mut prev = Pixel { r: 0, g: 0, b: 0 };
+    let mut run = 0u8;
+    let last = pixels.len().saturating_sub(1);
+
+    for (i, &px) in pixels.iter().enumerate() {
+        if px == prev {
+            run += 1;
+            if run == 62 || i == last {
+                out.push(QOI_OP_RUN | (run - 1));
+                run = 0;
+            }
+        } else {
+            if run > 0 {
+                out.push(QOI_OP_RUN | (run - 1));
+                run = 0;
+            }
+
+            let idx = hash(px);
+            if index[idx] == px {
+                out.push(QOI_OP_INDEX | idx as u8);
+            } else {
+                let dr = px.r.wrapping_sub(prev.r) as i8;
+                let dg = px.g.wrapping_sub(prev.g) as i8;
+                let db = px.b.wrapping_sub(prev.b) as i8;
+                let dr_dg = dr.wrapping_sub(dg);
+                let db_dg = db.wrapping_sub(dg);
+
+                if (-2..=1).contains(&dr) && (-2..=1).contains(&dg) && (-2..=1).contains(&db) {
+                    out.push(
+                        QOI_OP_DIFF
+                            | (((dr + 2) as u8) << 4)
+                            | (((dg + 2) as u8) << 2)
+                            | ((db + 2) as u8),
+                    );
+                } else if (-32..=31).contains(&dg) && (-8..=7).contains(&dr_dg) && (-8..=7).contains(&db_dg)
+                {
+                    out.push(QOI_OP_LUMA | (dg + 32) as u8);
+                    out.push((((dr_dg + 8) as u8) << 4) | ((db_dg + 8) as u8));
+                } else {
+                    out.push(QOI_OP_RGB);
+                    out.push(px.r);
+                    out.push(px.g);
+                    out.push(px.b);
+                }
+            }
+
+            index[idx] = px;
+        }
+
+        prev = px;
+    }
+
+    out.extend_from_slice(&END_MARKER);
+    out
+}
+
+/// Reads the next byte at `pos`, advancing it, or errors on truncated input
+/// instead of panicking.
+fn read_byte(bytes: &[u8], pos: &mut usize) -> Result<u8, String> {
+    let byte = *bytes.get(*pos).ok_or_else(|| "truncated qoi stream".to_string())?;
+    *pos += 1;
+    Ok(byte)
+}
+
+/// Decodes a QOI byte stream back into an RGB8 image. Errors (rather than
+/// panicking) on truncated input, e.g. a damaged cache file.
+pub fn decode(bytes: &[u8]) -> Result<RgbImage, String> {
+    if bytes.len() < HEADER_LEN || &bytes[0..4] != b"qoif" {
+        return Err("not a qoi stream".to_string());
+    }
+
+    let width = u32::from_be_bytes(bytes[4..8].try_into().unwrap());
+    let height = u32::from_be_bytes(bytes[8..12].try_into().unwrap());
+    let n_pixels = width as usize * height as usize;
+
+    let mut index = [Pixel::default(); 64];
+    let mut prev = Pixel { r: 0, g: 0, b: 0 };
+    let mut run = 0u8;
+    let mut pos = HEADER_LEN;
+    let mut buf = Vec::with_capacity(n_pixels * 3);
+
+    while buf.len() / 3 < n_pixels {
+        let px = if run > 0 {
+            run -= 1;
+            prev
+        } else {
+            let byte = read_byte(bytes, &mut pos)?;
+
+            if byte == QOI_OP_RGB {
+                Pixel {
+                    r: read_byte(bytes, &mut pos)?,
+                    g: read_byte(bytes, &mut pos)?,
+                    b: read_byte(bytes, &mut pos)?,
+                }
+            } else {
+                match byte & QOI_MASK_2 {
+                    QOI_OP_INDEX => index[(byte & 0x3f) as usize],
+                    QOI_OP_DIFF => Pixel {
+                        r: prev.r.wrapping_add((((byte >> 4) & 0x03) as i8 - 2) as u8),
+                        g: prev.g.wrapping_add((((byte >> 2) & 0x03) as i8 - 2) as u8),
+                        b: prev.b.wrapping_add(((byte & 0x03) as i8 - 2) as u8),
+                    },
+                    QOI_OP_LUMA => {
+                        let byte2 = read_byte(bytes, &mut pos)?;
+                        let dg = (byte & 0x3f) as i8 - 32;
+                        let dr = dg.wrapping_add(((byte2 >> 4) & 0x0f) as i8 - 8);
+                        let db = dg.wrapping_add((byte2 & 0x0f) as i8 - 8);
+                        Pixel {
+                            r: prev.r.wrapping_add(dr as u8),
+                            g: prev.g.wrapping_add(dg as u8),
+                            b: prev.b.wrapping_add(db as u8),
+                        }
+                    }
+                    QOI_OP_RUN => {
+                        run = byte & 0x3f;
+                        prev
+                    }
+                    _ => unreachable!("only the 2-bit tags above are possible here"),
+                }
+            }
+        };
+
+        index[hash(px)] = px;
+        buf.push(px.r);
+        buf.push(px.g);
+        buf.push(px.b);
+        prev = px;
+    }
+
+    RgbImage::from_raw(width, height, buf).ok_or_else(|| "decoded buffer had the wrong size".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roundtrip(img: &RgbImage) {
+        let decoded = decode(&encode(img)).expect("failed to decode a freshly encoded image");
+        assert_eq!(decoded.dimensions(), img.dimensions());
+        assert_eq!(decoded.into_raw(), img.clone().into_raw());
+    }
+
+    #[test]
+    fn roundtrips_a_solid_color() {
+        roundtrip(&RgbImage::from_pixel(16, 16, image::Rgb([10, 20, 30])));
+    }
+
+    #[test]
+    fn roundtrips_a_run_longer_than_62_pixels() {
+        roundtrip(&RgbImage::from_pixel(100, 2, image::Rgb([200, 0, 128])));
+    }
+
+    #[test]
+    fn roundtrips_a_gradient() {
+        let img = RgbImage::from_fn(64, 64, |x, y| image::Rgb([x as u8, y as u8, (x + y) as u8]));
+        roundtrip(&img);
+    }
+
+    #[test]
+    fn decode_errors_on_truncated_input() {
+        let bytes = encode(&RgbImage::from_pixel(32, 32, image::Rgb([5, 6, 7])));
+        assert!(decode(&bytes[..bytes.len() / 2]).is_err());
+    }
+}
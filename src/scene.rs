@@ -0,0 +1,155 @@
+//! Flags scene cuts from per-frame LAB diff scores via a rolling mean/stddev
+//! threshold.
+
+use image::{Pixel, RgbImage};
+use lab::Lab;
+use std::collections::VecDeque;
+
+/// A detected scene cut.
+#[derive(Clone, Copy, Debug)]
+pub struct Cut {
+    /// Index of the frame on the "after" side of the boundary.
+    pub frame: usize,
+    /// Timestamp in seconds, derived from `frame / fps`.
+    pub timestamp: f64,
+    /// The score that tripped the threshold, kept around for diagnostics.
+    pub score: f32,
+}
+
+/// Sum of squared LAB distances between two equally-sized frames, divided by
+/// pixel count.
+pub fn frame_score(prev: &RgbImage, curr: &RgbImage) -> f32 {
+    assert_eq!(prev.dimensions(), curr.dimensions(), "frame size changed mid-video");
+
+    let mut total = 0f32;
+    let mut count = 0usize;
+
+    for (p, c) in prev.pixels().zip(curr.pixels()) {
+        let p = p.channels();
+        let c = c.channels();
+        let lab_p = Lab::from_rgb(&[p[0], p[1], p[2]]);
+        let lab_c = Lab::from_rgb(&[c[0], c[1], c[2]]);
+
+        total += lab_p.squared_distance(&lab_c);
+        count += 1;
+    }
+
+    total / count as f32
+}
+
+/// Rolling-window scene cut detector.
+///
+/// Feed per-frame scores in order via [`SceneDetector::push`]. A frame is
+/// flagged as a cut when its score exceeds `mean + k * stddev` of the
+/// trailing window, and at least `min_gap` frames have elapsed since the
+/// previous cut (this suppresses flicker/fade false positives).
+pub struct SceneDetector {
+    k: f32,
+    min_gap: usize,
+    window: usize,
+    scores: VecDeque<f32>,
+    frame: usize,
+    last_cut: Option<usize>,
+    fps: f64,
+}
+
+impl SceneDetector {
+    pub fn new(k: f32, min_gap: usize, window: usize, fps: f64) -> Self {
+        SceneDetector {
+            k,
+            min_gap,
+            window,
+            scores: VecDeque::with_capacity(window),
+            frame: 0,
+            last_cut: None,
+            fps,
+        }
+    }
+
+    /// Scores the next frame and advances the detector, returning `Some(Cut)`
+    /// if this frame trips the threshold.
+    pub fn push(&mut self, score: f32) -> Option<Cut> {
+        self.frame += 1;
+        let frame = self.frame;
+
+        let is_cut = if self.scores.len() >= 2 {
+            let mean = self.scores.iter().sum::<f32>() / self.scores.len() as f32;
+            let variance =
+                self.scores.iter().map(|s| (s - mean).powi(2)).sum::<f32>() / self.scores.len() as f32;
+            let threshold = mean + self.k * variance.sqrt();
+
+            let gap_ok = self.last_cut.map_or(true, |last| frame - last >= self.min_gap);
+
+            score > threshold && gap_ok
+        } else {
+            false
+        };
+
+        if self.scores.len() == self.window {
+            self.scores.pop_front();
+        }
+        self.scores.push_back(score);
+
+        if is_cut {
+            self.last_cut = Some(frame);
+            Some(Cut {
+                frame,
+                timestamp: frame as f64 / self.fps,
+                score,
+            })
+        } else {
+            None
+        }
+    }
+}
+
+/// Runs the detector over a full sequence of frames, returning every cut in
+/// order.
+pub fn detect_cuts(frames: &[RgbImage], k: f32, min_gap: usize, fps: f64) -> Vec<Cut> {
+    let mut detector = SceneDetector::new(k, min_gap, 30, fps);
+    let mut cuts = vec![];
+
+    for pair in frames.windows(2) {
+        let score = frame_score(&pair[0], &pair[1]);
+        if let Some(cut) = detector.push(score) {
+            cuts.push(cut);
+        }
+    }
+
+    cuts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flat_scores_never_cut() {
+        let mut detector = SceneDetector::new(3.0, 2, 30, 30.0);
+        for _ in 0..20 {
+            assert!(detector.push(10.0).is_none());
+        }
+    }
+
+    #[test]
+    fn a_spike_past_the_threshold_cuts() {
+        let mut detector = SceneDetector::new(3.0, 2, 30, 30.0);
+        for _ in 0..10 {
+            assert!(detector.push(10.0).is_none());
+        }
+
+        let cut = detector.push(500.0).expect("spike should trip the threshold");
+        assert_eq!(cut.frame, 11);
+    }
+
+    #[test]
+    fn min_gap_suppresses_a_second_cut() {
+        let mut detector = SceneDetector::new(3.0, 5, 30, 30.0);
+        for _ in 0..10 {
+            assert!(detector.push(10.0).is_none());
+        }
+
+        assert!(detector.push(500.0).is_some());
+        assert!(detector.push(500.0).is_none());
+    }
+}
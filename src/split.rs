@@ -0,0 +1,119 @@
+//! Crops an image into sub-images at detected cut lines.
+
+use crate::{Direction, Line};
+use image::RgbImage;
+use std::path::Path;
+
+/// A cropped tile's rectangle within the source image.
+#[derive(Clone, Copy, Debug)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Promotes the lines that span at least `span_fraction` of the relevant
+/// dimension into cut coordinates, clusters cuts within `cluster_distance`
+/// pixels of each other into one boundary, crops `img` into the resulting
+/// grid of tiles, writes each tile into `output_dir`, and returns their
+/// rectangles in row-major order.
+pub fn split_into_tiles(
+    lines: &[Line],
+    img: &RgbImage,
+    span_fraction: f32,
+    cluster_distance: usize,
+    output_dir: impl AsRef<Path>,
+) -> std::io::Result<Vec<Rect>> {
+    let width = img.width() as usize;
+    let height = img.height() as usize;
+
+    let x_cuts: Vec<usize> = lines
+        .iter()
+        .filter(|l| matches!(l.dir, Direction::Vertical) && l.len as f32 >= span_fraction * height as f32)
+        .map(|l| l.x)
+        .collect();
+    let y_cuts: Vec<usize> = lines
+        .iter()
+        .filter(|l| matches!(l.dir, Direction::Horizontal) && l.len as f32 >= span_fraction * width as f32)
+        .map(|l| l.y)
+        .collect();
+
+    let x_bounds = boundaries(x_cuts, cluster_distance, width);
+    let y_bounds = boundaries(y_cuts, cluster_distance, height);
+
+    std::fs::create_dir_all(&output_dir)?;
+
+    let mut rects = vec![];
+
+    for (row, y_win) in y_bounds.windows(2).enumerate() {
+        for (col, x_win) in x_bounds.windows(2).enumerate() {
+            let rect = Rect {
+                x: x_win[0] as u32,
+                y: y_win[0] as u32,
+                width: (x_win[1] - x_win[0]) as u32,
+                height: (y_win[1] - y_win[0]) as u32,
+            };
+
+            if rect.width == 0 || rect.height == 0 {
+                continue;
+            }
+
+            let tile = image::imageops::crop_imm(img, rect.x, rect.y, rect.width, rect.height).to_image();
+            tile.save(output_dir.as_ref().join(format!("tile_{}_{}.png", row, col)))
+                .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))?;
+
+            rects.push(rect);
+        }
+    }
+
+    Ok(rects)
+}
+
+/// Sorts `cuts`, clusters the ones within `cluster_distance` of each other
+/// into a single boundary, and wraps the result with `0` and `extent` so the
+/// boundaries fully partition the axis.
+fn boundaries(mut cuts: Vec<usize>, cluster_distance: usize, extent: usize) -> Vec<usize> {
+    cuts.sort_unstable();
+
+    let mut clustered = vec![0usize];
+
+    for cut in cuts {
+        if cut == 0 || cut >= extent {
+            continue;
+        }
+
+        match clustered.last() {
+            Some(&last) if cut.saturating_sub(last) <= cluster_distance => {}
+            _ => clustered.push(cut),
+        }
+    }
+
+    clustered.push(extent);
+    clustered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drops_cuts_at_the_edges() {
+        assert_eq!(boundaries(vec![0, 100], 5, 100), vec![0, 100]);
+    }
+
+    #[test]
+    fn clusters_cuts_within_distance() {
+        assert_eq!(boundaries(vec![20, 23, 60], 5, 100), vec![0, 20, 60, 100]);
+    }
+
+    #[test]
+    fn keeps_cuts_farther_apart_than_cluster_distance() {
+        assert_eq!(boundaries(vec![20, 40], 5, 100), vec![0, 20, 40, 100]);
+    }
+
+    #[test]
+    fn no_cuts_yields_a_single_span() {
+        assert_eq!(boundaries(vec![], 5, 100), vec![0, 100]);
+    }
+}